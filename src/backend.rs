@@ -0,0 +1,443 @@
+use crate::database::MigrationEntry;
+use crate::error::Error;
+
+/// A storage backend capable of holding the migration ledger and applying
+/// migrations against it. Implementations own their own connection and model
+/// transactions by emitting the backend's native `BEGIN`/`COMMIT`/`ROLLBACK`
+/// statements, so no driver-specific transaction type ever escapes this trait.
+///
+/// The ledger table name is injected at connect time so it can be overridden
+/// from `portunus.toml`; the historical default is `__portunus_migrations`.
+pub trait MigrationBackend {
+    /// Create the ledger table (and its index) if it does not already exist.
+    fn create_schema(&mut self) -> Result<(), Error>;
+    /// Read every applied migration row.
+    fn fetch_existing_migrations(&mut self) -> Result<Vec<MigrationEntry>, Error>;
+    /// Read the `n` most recently applied migrations, newest first.
+    fn fetch_applied_migrations_desc(&mut self, n: u32) -> Result<Vec<MigrationEntry>, Error>;
+
+    fn begin(&mut self) -> Result<(), Error>;
+    fn commit(&mut self) -> Result<(), Error>;
+    fn rollback(&mut self) -> Result<(), Error>;
+
+    /// Execute one or more statements as a single batch.
+    fn batch_execute(&mut self, sql: &str) -> Result<(), Error>;
+    /// Insert a ledger row for a freshly applied migration, returning its id.
+    fn insert_migration(&mut self, entry: &MigrationEntry) -> Result<i64, Error>;
+    /// Drop the ledger row for a rolled-back migration.
+    fn delete_migration(&mut self, filename: &str) -> Result<(), Error>;
+
+    /// Whether the backend can wrap DDL in a transaction. MySQL cannot, which
+    /// changes how `run_migration` batches work and whether a mid-run failure
+    /// can be auto-rolled-back.
+    fn supports_transactional_ddl(&self) -> bool {
+        true
+    }
+}
+
+/// Select and connect a backend from the URL scheme, recording ledger rows in
+/// `table`. The arms are gated on the cargo feature for each driver, so a scheme
+/// whose backend was not compiled in falls through to
+/// [`Error::UnsupportedScheme`].
+pub fn backend_for_url(db_url: &str, table: &str) -> Result<Box<dyn MigrationBackend>, Error> {
+    let scheme = db_url.split("://").next().unwrap_or("");
+    match scheme {
+        #[cfg(feature = "postgres")]
+        "postgres" | "postgresql" => Ok(Box::new(postgres_backend::PostgresBackend::connect(
+            db_url, table,
+        )?)),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Ok(Box::new(sqlite_backend::SqliteBackend::connect(db_url, table)?)),
+        #[cfg(feature = "mysql")]
+        "mysql" => Ok(Box::new(mysql_backend::MysqlBackend::connect(db_url, table)?)),
+        other => Err(Error::UnsupportedScheme(other.to_string())),
+    }
+}
+
+/// Name of the index created alongside the ledger table.
+fn index_name(table: &str) -> String {
+    format!("__idx_{}_files", table.trim_start_matches("__"))
+}
+
+#[cfg(feature = "postgres")]
+pub mod postgres_backend {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use postgres::{Client, NoTls};
+    use std::time::SystemTime;
+
+    pub struct PostgresBackend {
+        conn: Client,
+        table: String,
+    }
+
+    impl PostgresBackend {
+        pub fn connect(db_url: &str, table: &str) -> Result<Self, Error> {
+            let conn = match Client::connect(db_url, NoTls) {
+                Ok(conn) => conn,
+                Err(e) => {
+                    eprintln!("Error connecting to database: {}", e);
+
+                    // Postgres reports a missing database rather than creating
+                    // it; mirror the CLI's historical behaviour of creating it
+                    // on the fly via the system `postgres` database.
+                    if e.to_string().contains("does not exist") {
+                        let (base_url, db_name) = db_url.rsplit_once('/').unwrap();
+                        let system_db_url = format!("{}/postgres", base_url);
+
+                        eprintln!("Attempting to create missing database `{}`...", db_name);
+
+                        let mut sys_client = Client::connect(&system_db_url, NoTls)?;
+                        sys_client.execute(&format!("CREATE DATABASE \"{}\";", db_name), &[])?;
+                        drop(sys_client);
+
+                        Client::connect(db_url, NoTls)?
+                    } else {
+                        return Err(e.into());
+                    }
+                }
+            };
+            Ok(PostgresBackend {
+                conn,
+                table: table.to_string(),
+            })
+        }
+
+        fn row_to_entry(row: &postgres::Row) -> MigrationEntry {
+            let timestamp: SystemTime = row.get("timestamp");
+            let timestamp: DateTime<Utc> = timestamp.into();
+            MigrationEntry {
+                id: row.get("id"),
+                filename: row.get("filename"),
+                hash: row.get("hash"),
+                has_down: row.get("has_down"),
+                timestamp: Some(timestamp),
+                down_sql_path: None,
+            }
+        }
+    }
+
+    impl MigrationBackend for PostgresBackend {
+        fn create_schema(&mut self) -> Result<(), Error> {
+            self.conn.batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {table} (
+                    id BIGSERIAL PRIMARY KEY,
+                    filename TEXT NOT NULL UNIQUE,
+                    hash TEXT NOT NULL,
+                    has_down BOOLEAN NOT NULL DEFAULT FALSE,
+                    timestamp TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+                );",
+                table = self.table
+            ))?;
+            self.conn.batch_execute(&format!(
+                "CREATE INDEX IF NOT EXISTS {idx} ON {table} (filename, hash)",
+                idx = index_name(&self.table),
+                table = self.table
+            ))?;
+            Ok(())
+        }
+
+        fn fetch_existing_migrations(&mut self) -> Result<Vec<MigrationEntry>, Error> {
+            let rows = self.conn.query(
+                &format!("SELECT id,filename,hash,has_down,timestamp FROM {}", self.table),
+                &[],
+            )?;
+            Ok(rows.iter().map(Self::row_to_entry).collect())
+        }
+
+        fn fetch_applied_migrations_desc(&mut self, n: u32) -> Result<Vec<MigrationEntry>, Error> {
+            let rows = self.conn.query(
+                &format!(
+                    "SELECT id,filename,hash,has_down,timestamp FROM {} ORDER BY id DESC LIMIT $1",
+                    self.table
+                ),
+                &[&(n as i64)],
+            )?;
+            Ok(rows.iter().map(Self::row_to_entry).collect())
+        }
+
+        fn begin(&mut self) -> Result<(), Error> {
+            self.conn.batch_execute("BEGIN")?;
+            Ok(())
+        }
+
+        fn commit(&mut self) -> Result<(), Error> {
+            self.conn.batch_execute("COMMIT")?;
+            Ok(())
+        }
+
+        fn rollback(&mut self) -> Result<(), Error> {
+            self.conn.batch_execute("ROLLBACK")?;
+            Ok(())
+        }
+
+        fn batch_execute(&mut self, sql: &str) -> Result<(), Error> {
+            self.conn.batch_execute(sql)?;
+            Ok(())
+        }
+
+        fn insert_migration(&mut self, entry: &MigrationEntry) -> Result<i64, Error> {
+            let row = self.conn.query_one(
+                &format!(
+                    "INSERT INTO {} (filename, hash, has_down) VALUES ($1, $2, $3) RETURNING id",
+                    self.table
+                ),
+                &[&entry.filename, &entry.hash, &entry.has_down],
+            )?;
+            Ok(row.get("id"))
+        }
+
+        fn delete_migration(&mut self, filename: &str) -> Result<(), Error> {
+            self.conn.execute(
+                &format!("DELETE FROM {} WHERE filename = $1", self.table),
+                &[&filename],
+            )?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite_backend {
+    use super::*;
+    use chrono::{DateTime, NaiveDateTime, Utc};
+    use rusqlite::Connection;
+
+    pub struct SqliteBackend {
+        conn: Connection,
+        table: String,
+    }
+
+    impl SqliteBackend {
+        pub fn connect(db_url: &str, table: &str) -> Result<Self, Error> {
+            // `sqlite://path/to.db` — strip the scheme to get the file path.
+            let path = db_url.trim_start_matches("sqlite://");
+            Ok(SqliteBackend {
+                conn: Connection::open(path)?,
+                table: table.to_string(),
+            })
+        }
+
+        fn parse_timestamp(raw: String) -> DateTime<Utc> {
+            NaiveDateTime::parse_from_str(&raw, "%Y-%m-%d %H:%M:%S")
+                .map(|naive| DateTime::from_naive_utc_and_offset(naive, Utc))
+                .unwrap_or_else(|_| Utc::now())
+        }
+
+        fn query_entries(&self, sql: &str) -> Result<Vec<MigrationEntry>, Error> {
+            let mut stmt = self.conn.prepare(sql)?;
+            let entries = stmt
+                .query_map([], |row| {
+                    Ok(MigrationEntry {
+                        id: Some(row.get::<_, i64>("id")?),
+                        filename: row.get("filename")?,
+                        hash: row.get("hash")?,
+                        has_down: row.get("has_down")?,
+                        timestamp: Some(Self::parse_timestamp(row.get("timestamp")?)),
+                        down_sql_path: None,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(entries)
+        }
+    }
+
+    impl MigrationBackend for SqliteBackend {
+        fn create_schema(&mut self) -> Result<(), Error> {
+            self.conn.execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {table} (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    filename TEXT NOT NULL UNIQUE,
+                    hash TEXT NOT NULL,
+                    has_down BOOLEAN NOT NULL DEFAULT 0,
+                    timestamp DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+                );",
+                table = self.table
+            ))?;
+            self.conn.execute_batch(&format!(
+                "CREATE INDEX IF NOT EXISTS {idx} ON {table} (filename, hash)",
+                idx = index_name(&self.table),
+                table = self.table
+            ))?;
+            Ok(())
+        }
+
+        fn fetch_existing_migrations(&mut self) -> Result<Vec<MigrationEntry>, Error> {
+            self.query_entries(&format!(
+                "SELECT id,filename,hash,has_down,timestamp FROM {}",
+                self.table
+            ))
+        }
+
+        fn fetch_applied_migrations_desc(&mut self, n: u32) -> Result<Vec<MigrationEntry>, Error> {
+            self.query_entries(&format!(
+                "SELECT id,filename,hash,has_down,timestamp FROM {} ORDER BY id DESC LIMIT {}",
+                self.table, n
+            ))
+        }
+
+        fn begin(&mut self) -> Result<(), Error> {
+            self.conn.execute_batch("BEGIN")?;
+            Ok(())
+        }
+
+        fn commit(&mut self) -> Result<(), Error> {
+            self.conn.execute_batch("COMMIT")?;
+            Ok(())
+        }
+
+        fn rollback(&mut self) -> Result<(), Error> {
+            self.conn.execute_batch("ROLLBACK")?;
+            Ok(())
+        }
+
+        fn batch_execute(&mut self, sql: &str) -> Result<(), Error> {
+            self.conn.execute_batch(sql)?;
+            Ok(())
+        }
+
+        fn insert_migration(&mut self, entry: &MigrationEntry) -> Result<i64, Error> {
+            self.conn.execute(
+                &format!(
+                    "INSERT INTO {} (filename, hash, has_down) VALUES (?1, ?2, ?3)",
+                    self.table
+                ),
+                rusqlite::params![entry.filename, entry.hash, entry.has_down],
+            )?;
+            Ok(self.conn.last_insert_rowid())
+        }
+
+        fn delete_migration(&mut self, filename: &str) -> Result<(), Error> {
+            self.conn.execute(
+                &format!("DELETE FROM {} WHERE filename = ?1", self.table),
+                rusqlite::params![filename],
+            )?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "mysql")]
+pub mod mysql_backend {
+    use super::*;
+    use chrono::{DateTime, NaiveDateTime, Utc};
+    use mysql::prelude::*;
+    use mysql::{Conn, Row};
+
+    pub struct MysqlBackend {
+        conn: Conn,
+        table: String,
+    }
+
+    impl MysqlBackend {
+        pub fn connect(db_url: &str, table: &str) -> Result<Self, Error> {
+            Ok(MysqlBackend {
+                conn: Conn::new(db_url)?,
+                table: table.to_string(),
+            })
+        }
+
+        fn row_to_entry(mut row: Row) -> MigrationEntry {
+            let raw_ts: String = row.take("timestamp").unwrap_or_default();
+            let timestamp = NaiveDateTime::parse_from_str(&raw_ts, "%Y-%m-%d %H:%M:%S")
+                .map(|naive| DateTime::from_naive_utc_and_offset(naive, Utc))
+                .unwrap_or_else(|_| Utc::now());
+            MigrationEntry {
+                id: row.take("id"),
+                filename: row.take("filename").unwrap_or_default(),
+                hash: row.take("hash").unwrap_or_default(),
+                has_down: row.take("has_down").unwrap_or(false),
+                timestamp: Some(timestamp),
+                down_sql_path: None,
+            }
+        }
+    }
+
+    impl MigrationBackend for MysqlBackend {
+        fn create_schema(&mut self) -> Result<(), Error> {
+            self.conn.query_drop(format!(
+                "CREATE TABLE IF NOT EXISTS {table} (
+                    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+                    filename VARCHAR(255) NOT NULL UNIQUE,
+                    hash TEXT NOT NULL,
+                    has_down BOOLEAN NOT NULL DEFAULT FALSE,
+                    timestamp DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+                );",
+                table = self.table
+            ))?;
+            self.conn
+                .query_drop(format!(
+                    "CREATE INDEX {idx} ON {table} (filename, hash)",
+                    idx = index_name(&self.table),
+                    table = self.table
+                ))
+                // Re-running against an existing table raises a duplicate
+                // key-name error; MySQL has no `CREATE INDEX IF NOT EXISTS`.
+                .or(Ok(()))
+        }
+
+        fn fetch_existing_migrations(&mut self) -> Result<Vec<MigrationEntry>, Error> {
+            let rows: Vec<Row> = self.conn.query(format!(
+                "SELECT id,filename,hash,has_down,timestamp FROM {}",
+                self.table
+            ))?;
+            Ok(rows.into_iter().map(Self::row_to_entry).collect())
+        }
+
+        fn fetch_applied_migrations_desc(&mut self, n: u32) -> Result<Vec<MigrationEntry>, Error> {
+            let rows: Vec<Row> = self.conn.exec(
+                format!(
+                    "SELECT id,filename,hash,has_down,timestamp FROM {} ORDER BY id DESC LIMIT ?",
+                    self.table
+                ),
+                (n,),
+            )?;
+            Ok(rows.into_iter().map(Self::row_to_entry).collect())
+        }
+
+        fn begin(&mut self) -> Result<(), Error> {
+            self.conn.query_drop("START TRANSACTION")?;
+            Ok(())
+        }
+
+        fn commit(&mut self) -> Result<(), Error> {
+            self.conn.query_drop("COMMIT")?;
+            Ok(())
+        }
+
+        fn rollback(&mut self) -> Result<(), Error> {
+            self.conn.query_drop("ROLLBACK")?;
+            Ok(())
+        }
+
+        fn batch_execute(&mut self, sql: &str) -> Result<(), Error> {
+            self.conn.query_drop(sql)?;
+            Ok(())
+        }
+
+        fn insert_migration(&mut self, entry: &MigrationEntry) -> Result<i64, Error> {
+            self.conn.exec_drop(
+                format!(
+                    "INSERT INTO {} (filename, hash, has_down) VALUES (?, ?, ?)",
+                    self.table
+                ),
+                (&entry.filename, &entry.hash, entry.has_down),
+            )?;
+            Ok(self.conn.last_insert_id() as i64)
+        }
+
+        fn delete_migration(&mut self, filename: &str) -> Result<(), Error> {
+            self.conn.exec_drop(
+                format!("DELETE FROM {} WHERE filename = ?", self.table),
+                (filename,),
+            )?;
+            Ok(())
+        }
+
+        fn supports_transactional_ddl(&self) -> bool {
+            // MySQL implicitly commits on DDL, so a migration batch cannot be
+            // wrapped in one rollback-able transaction.
+            false
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! The command-line surface, shared by both the `portunus` and `portunus-rs`
+//! binaries. Keeping the argument definitions and the run loop here means the
+//! manifest/precedence logic lives in exactly one place.
+
+use crate::config;
+use crate::database::Database;
+use crate::migrations::{
+    create_new_migration, get_migration_status, rollback_migrations, run_migration,
+};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use dotenv::dotenv;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Cli {
+    #[arg(short, long)]
+    pub db_url: Option<String>,
+
+    #[arg(short, long)]
+    pub migrations_dir: Option<PathBuf>,
+
+    /// Environment block from portunus.toml to use
+    #[arg(short, long)]
+    pub env: Option<String>,
+
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Subcommand to execute
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Apply pending migrations
+    Migrate(MigrationArgs),
+    Status(StatusArgs),
+    New(NewMigrationArgs),
+    /// Roll back the last N applied migrations
+    Down(DownArgs),
+    /// Write a starter portunus.toml manifest
+    Init,
+    Reset,
+}
+
+#[derive(Args, Debug)]
+pub struct DownArgs {
+    /// Number of migrations to roll back, newest first
+    #[arg(short, long, default_value_t = 1)]
+    pub steps: u32,
+
+    #[arg(short, long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct MigrationArgs {
+    #[arg(short = 'n', long)]
+    pub dry_run: bool,
+
+    #[arg(short, long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct NewMigrationArgs {
+    /// Name for the new migration
+    pub name: String,
+}
+
+#[derive(Args, Debug)]
+pub struct StatusArgs {
+    /// Exit with a nonzero status if any migration is Pending or Changed
+    #[arg(short, long)]
+    pub check: bool,
+}
+
+/// Parse the process arguments and run. Entry point for both binaries.
+pub fn main() {
+    let _env = dotenv();
+    run(Cli::parse());
+}
+
+/// Resolve configuration and dispatch a parsed [`Cli`].
+pub fn run(args: Cli) {
+    // `init` only writes the manifest; it needs neither a connection nor an
+    // existing manifest.
+    if let Some(Commands::Init) = args.command {
+        match config::write_manifest(&PathBuf::from(config::MANIFEST_FILE)) {
+            Ok(()) => println!("Created {}", config::MANIFEST_FILE),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let cfg = config::Config::load(&PathBuf::from(config::MANIFEST_FILE))
+        .expect("Failed to load portunus.toml")
+        .unwrap_or_default();
+
+    let env = cfg.environment(args.env.as_deref());
+    if let Some(name) = args.env.as_deref() {
+        if env.is_none() {
+            eprintln!("No environment `{}` in {}", name, config::MANIFEST_FILE);
+            std::process::exit(1);
+        }
+    }
+
+    // Precedence throughout: explicit CLI flag > selected environment >
+    // top-level manifest > process env.
+    let db_url = args
+        .db_url
+        .clone()
+        .or_else(|| env.and_then(|e| e.database_url.clone()))
+        .or_else(|| cfg.database_url.clone())
+        .or_else(|| std::env::var("DATABASE_URL").ok())
+        .map(|url| config::interpolate_env(&url));
+    let db_url = match db_url {
+        Some(url) if !url.is_empty() => url,
+        _ => {
+            eprintln!("No database_url set. Pass --db-url, set DATABASE_URL, or add one to portunus.toml");
+            let _ = Cli::command().print_help();
+            std::process::exit(1);
+        }
+    };
+
+    let migrations_dir = args
+        .migrations_dir
+        .clone()
+        .or_else(|| env.and_then(|e| e.migrations_dir.clone()))
+        .or_else(|| cfg.migrations_dir.clone())
+        .unwrap_or_else(|| PathBuf::from("./migrations"));
+
+    let table = env
+        .and_then(|e| e.table.clone())
+        .or_else(|| cfg.table.clone())
+        .unwrap_or_else(|| config::DEFAULT_TABLE.to_string());
+
+    if migrations_dir.exists() && !migrations_dir.is_dir() {
+        eprintln!("Migrations directory does not exist or is not a directory");
+        std::process::exit(1);
+    }
+    if !migrations_dir.exists() {
+        std::fs::create_dir_all(&migrations_dir).unwrap();
+    }
+    let mut db = Database::init(&db_url, &table).expect("Failed to initialize database");
+
+    match args.command {
+        Some(Commands::Migrate(mig_args)) => {
+            run_migration(&mut db, &migrations_dir, mig_args.dry_run, mig_args.force);
+        }
+        Some(Commands::Status(status_args)) => {
+            get_migration_status(&db, &migrations_dir, status_args.check);
+        }
+        Some(Commands::Down(down_args)) => {
+            rollback_migrations(&mut db, &migrations_dir, down_args.steps, down_args.force);
+        }
+        Some(Commands::Reset) => {
+            db.reset(&db_url).expect("Failed to reset database");
+        }
+        Some(Commands::New(mig_args)) => {
+            create_new_migration(&migrations_dir, &mig_args.name);
+        }
+        Some(Commands::Init) => unreachable!("handled above"),
+        _ => {
+            let _ = Cli::command().print_help();
+        }
+    }
+}
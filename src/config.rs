@@ -0,0 +1,117 @@
+use crate::error::Error;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Historical, hard-coded ledger table name, used when the manifest does not
+/// override it.
+pub const DEFAULT_TABLE: &str = "__portunus_migrations";
+/// Default manifest filename written by `init` and read on startup.
+pub const MANIFEST_FILE: &str = "portunus.toml";
+
+/// A parsed `portunus.toml`. Top-level keys act as defaults; `[environments.*]`
+/// blocks override them when selected with `--env`.
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    pub database_url: Option<String>,
+    pub migrations_dir: Option<PathBuf>,
+    /// Override for the ledger table name.
+    pub table: Option<String>,
+    #[serde(default)]
+    pub environments: BTreeMap<String, Environment>,
+}
+
+/// A named environment block, e.g. `[environments.prod]`.
+#[derive(Debug, Default, Deserialize)]
+pub struct Environment {
+    pub database_url: Option<String>,
+    pub migrations_dir: Option<PathBuf>,
+    pub table: Option<String>,
+}
+
+impl Config {
+    /// Load a manifest from `path`, returning `None` if it does not exist.
+    pub fn load(path: &Path) -> Result<Option<Config>, Error> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let raw = fs::read_to_string(path)
+            .map_err(|e| Error::Message(format!("failed to read {:?}: {}", path, e)))?;
+        let config = toml::from_str(&raw)
+            .map_err(|e| Error::Message(format!("failed to parse {:?}: {}", path, e)))?;
+        Ok(Some(config))
+    }
+
+    /// Look up a named environment block.
+    pub fn environment(&self, name: Option<&str>) -> Option<&Environment> {
+        name.and_then(|n| self.environments.get(n))
+    }
+}
+
+/// Expand `${ENV_VAR}` references against the process environment; an unset
+/// variable expands to an empty string, matching shell behaviour.
+pub fn interpolate_env(input: &str) -> String {
+    let re = Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap();
+    re.replace_all(input, |caps: &regex::Captures| {
+        std::env::var(&caps[1]).unwrap_or_default()
+    })
+    .into_owned()
+}
+
+/// Write a starter `portunus.toml` to `path`. Refuses to clobber an existing
+/// manifest.
+pub fn write_manifest(path: &Path) -> Result<(), Error> {
+    if path.exists() {
+        return Err(Error::Message(format!("{:?} already exists", path)));
+    }
+    let template = r#"# portunus migration manifest
+database_url = "${DATABASE_URL}"
+migrations_dir = "./migrations"
+# table = "__portunus_migrations"
+
+[environments.staging]
+database_url = "${STAGING_DATABASE_URL}"
+
+[environments.prod]
+database_url = "${PROD_DATABASE_URL}"
+"#;
+    fs::write(path, template)
+        .map_err(|e| Error::Message(format!("failed to write {:?}: {}", path, e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_set_and_unset_vars() {
+        std::env::set_var("PORTUNUS_TEST_HOST", "db.example.com");
+        let out = interpolate_env("postgres://${PORTUNUS_TEST_HOST}/${PORTUNUS_TEST_MISSING}");
+        // A set variable expands; an unset one collapses to an empty string.
+        assert_eq!(out, "postgres://db.example.com/");
+    }
+
+    #[test]
+    fn parses_top_level_and_environment_blocks() {
+        let raw = r#"
+database_url = "top"
+migrations_dir = "./m"
+table = "ledger"
+
+[environments.prod]
+database_url = "prod-url"
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(cfg.database_url.as_deref(), Some("top"));
+        assert_eq!(cfg.table.as_deref(), Some("ledger"));
+
+        let prod = cfg.environment(Some("prod")).unwrap();
+        assert_eq!(prod.database_url.as_deref(), Some("prod-url"));
+        // An unknown name, or no `--env` at all, selects no block.
+        assert!(cfg.environment(Some("missing")).is_none());
+        assert!(cfg.environment(None).is_none());
+    }
+}
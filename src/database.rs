@@ -1,7 +1,9 @@
 use chrono::{DateTime, Utc};
-use postgres::{Client, NoTls, Transaction};
 use sha2::{Digest, Sha256};
-use std::{fmt::Debug, fs, path::Path, time::SystemTime};
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::backend::{backend_for_url, MigrationBackend};
+use crate::error::Error;
 
 #[derive(Debug, Clone)]
 pub struct MigrationEntry {
@@ -9,11 +11,34 @@ pub struct MigrationEntry {
     pub filename: String, // although this will be a path when reading, we only need file stem
     pub hash: String,
     pub timestamp: Option<DateTime<Utc>>,
+    /// Path to the `down.sql` reversal script, if this migration is a directory
+    /// that ships one. `None` for plain single-file migrations and for entries
+    /// read back from the ledger.
+    pub down_sql_path: Option<PathBuf>,
+    /// Whether a reversal script was recorded for this migration. For on-disk
+    /// entries this mirrors `down_sql_path.is_some()`; for entries read from
+    /// `__portunus_migrations` it comes from the stored `has_down` column.
+    pub has_down: bool,
 }
 
 impl MigrationEntry {
-    pub fn new(filename: &Path) -> Self {
-        let data = fs::read_to_string(&filename)
+    /// Build an entry from an on-disk migration. A migration is either a single
+    /// `.sql` file (current behaviour) or a directory holding `up.sql` and,
+    /// optionally, `down.sql`. Only the up script contributes to the hash.
+    pub fn new(path: &Path) -> Self {
+        let up_path = if path.is_dir() {
+            path.join("up.sql")
+        } else {
+            path.to_path_buf()
+        };
+        let down_sql_path = if path.is_dir() {
+            let down = path.join("down.sql");
+            down.exists().then_some(down)
+        } else {
+            None
+        };
+
+        let data = fs::read_to_string(&up_path)
             .expect("Failed to read file")
             .trim()
             .to_string();
@@ -35,7 +60,7 @@ impl MigrationEntry {
         hasher.update(&cleaned_sql);
         let hash: String = format!("{:X}", hasher.finalize());
 
-        let filename = filename
+        let filename = path
             .file_name()
             .expect("Failed to get filename")
             .to_str()
@@ -47,152 +72,154 @@ impl MigrationEntry {
             filename,
             hash,
             timestamp: None,
+            has_down: down_sql_path.is_some(),
+            down_sql_path,
         }
     }
 }
+/// The ledger owner. `Database` is now a thin façade over a boxed
+/// [`MigrationBackend`]; the concrete driver (Postgres, SQLite, MySQL) is chosen
+/// from the connection URL scheme in [`Database::init`].
 pub struct Database {
-    conn: Client,
+    backend: Box<dyn MigrationBackend>,
     migrations: Vec<MigrationEntry>,
 }
 
 impl Database {
-    /// THIS DOES NOT WORK FOR SUPABASE, USE THE CLI TO RESET YOUR DB
-    pub fn reset(mut self, db_url: &str) -> Result<(), postgres::Error> {
-        let db_name = db_url.rsplitn(2, '/').next().unwrap();
-        println!("Resetting database: {}", db_name);
-
-        drop(self.conn);
-
-        let base_url = db_url.rsplitn(2, '/').nth(1).unwrap();
-        let sys_db_url = format!("{}/postgres", base_url);
-
-        // Connect to the system database
-        let mut sys_conn = postgres::Client::connect(&sys_db_url, postgres::NoTls)?;
-
-        // Terminate other connections to the target DB
-        let disconnect_query = format!(
-            "SELECT pg_terminate_backend(pid) \
-         FROM pg_stat_activity \
-         WHERE datname = '{}' AND pid <> pg_backend_pid();",
-            db_name
-        );
-        sys_conn.execute(&disconnect_query, &[])?;
+    /// Connect and prepare the ledger, recording rows in `table` (defaults to
+    /// `__portunus_migrations` at the call site).
+    pub fn init(db_url: &str, table: &str) -> Result<Database, Error> {
+        let mut backend = backend_for_url(db_url, table)?;
+        backend.create_schema()?;
+        let migrations = backend.fetch_existing_migrations()?;
+        Ok(Database {
+            backend,
+            migrations,
+        })
+    }
 
-        // Drop and recreate the target database
-        let drop_query = format!("DROP DATABASE IF EXISTS \"{}\";", db_name);
-        sys_conn.execute(&drop_query, &[])?;
+    /// THIS DOES NOT WORK FOR SUPABASE, USE THE CLI TO RESET YOUR DB
+    ///
+    /// Dropping and recreating the target database is Postgres-specific, so this
+    /// is only implemented when the `postgres` backend is compiled in.
+    pub fn reset(self, db_url: &str) -> Result<(), Error> {
+        // Release our own connection before touching the system database.
+        drop(self);
+
+        #[cfg(feature = "postgres")]
+        {
+            use postgres::{Client, NoTls};
+
+            let (base_url, db_name) = db_url.rsplit_once('/').unwrap();
+            println!("Resetting database: {}", db_name);
+
+            let sys_db_url = format!("{}/postgres", base_url);
+
+            // Connect to the system database
+            let mut sys_conn = Client::connect(&sys_db_url, NoTls)?;
+
+            // Terminate other connections to the target DB
+            let disconnect_query = format!(
+                "SELECT pg_terminate_backend(pid) \
+             FROM pg_stat_activity \
+             WHERE datname = '{}' AND pid <> pg_backend_pid();",
+                db_name
+            );
+            sys_conn.execute(&disconnect_query, &[])?;
 
-        let create_query = format!("CREATE DATABASE \"{}\";", db_name);
-        sys_conn.execute(&create_query, &[])?;
+            // Drop and recreate the target database
+            sys_conn.execute(&format!("DROP DATABASE IF EXISTS \"{}\";", db_name), &[])?;
+            sys_conn.execute(&format!("CREATE DATABASE \"{}\";", db_name), &[])?;
 
-        println!("✓ Database `{}` has been reset.", db_name);
-        Ok(())
+            println!("✓ Database `{}` has been reset.", db_name);
+            Ok(())
+        }
+        #[cfg(not(feature = "postgres"))]
+        {
+            let _ = db_url;
+            Err(Error::Message(
+                "reset is only implemented for the postgres backend".into(),
+            ))
+        }
     }
+
     pub fn get_migrations(&self) -> &Vec<MigrationEntry> {
         &self.migrations
     }
 
-    pub fn start_transaction(&mut self) -> Result<Transaction, postgres::Error> {
-        self.conn.transaction()
+    /// Re-read the ledger into the cached snapshot. Callers that apply
+    /// migrations and then keep using the same `Database` (e.g. a long-lived
+    /// [`crate::Runner`]) must refresh so a later pass sees the rows it just
+    /// wrote rather than re-attempting them.
+    pub fn refresh(&mut self) -> Result<(), Error> {
+        self.migrations = self.backend.fetch_existing_migrations()?;
+        Ok(())
     }
-    fn create_schema(conn: &mut Client) -> Result<(), postgres::Error> {
-        let query = "
-            CREATE TABLE IF NOT EXISTS __portunus_migrations (
-                id BIGSERIAL PRIMARY KEY,
-                filename TEXT NOT NULL UNIQUE,
-                hash TEXT NOT NULL,
-                timestamp TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
-            );
-        ";
 
-        conn.execute(query, &[])?;
+    pub fn supports_transactional_ddl(&self) -> bool {
+        self.backend.supports_transactional_ddl()
+    }
 
-        let create_index_on_filename = "CREATE INDEX IF NOT EXISTS __idx_portunus_files ON __portunus_migrations (filename, hash)";
-        conn.execute(create_index_on_filename, &[])?;
-        Ok(())
+    pub fn begin(&mut self) -> Result<(), Error> {
+        self.backend.begin()
     }
 
-    pub fn fetch_existing_migrations(
-        conn: &mut Client,
-    ) -> Result<Vec<MigrationEntry>, postgres::Error> {
-        let query = "SELECT id,filename,hash,timestamp FROM __portunus_migrations";
-        let rows = conn.query(query, &[])?;
-
-        let entry = rows
-            .iter()
-            .map(|row| {
-                let id = row.get("id");
-                let filename = row.get("filename");
-                let hash = row.get("hash");
-                let timestamp: SystemTime = row.get("timestamp");
-                let timestamp: DateTime<Utc> = timestamp.into();
-                MigrationEntry {
-                    id,
-                    filename,
-                    hash,
-                    timestamp: Some(timestamp),
-                }
-            })
-            .collect::<Vec<MigrationEntry>>();
-        Ok(entry)
+    pub fn commit(&mut self) -> Result<(), Error> {
+        self.backend.commit()
     }
-    pub fn init(db_url: &str) -> Result<Database, postgres::Error> {
-        // Try connecting to the target DB
-        match Client::connect(db_url, NoTls) {
-            Ok(mut client) => {
-                Self::create_schema(&mut client)?;
-                let migrations = Self::fetch_existing_migrations(&mut client)?;
-                Ok(Database {
-                    conn: client,
-                    migrations,
-                })
-            }
-            Err(e) => {
-                eprintln!("Error connecting to database: {}", e);
-
-                // Check if the error is due to missing database
-                if e.to_string().contains("does not exist") {
-                    // Extract db name
-                    let db_name = db_url.rsplitn(2, '/').next().unwrap();
-                    let system_db_url = format!("{}/postgres", db_url.rsplitn(2, '/').nth(1).unwrap());
-
-                    eprintln!("Attempting to create missing database `{}`...", db_name);
-
-                    // Connect to the system database
-                    let mut sys_client = Client::connect(&system_db_url, NoTls)?;
-
-                    // Create the target database
-                    sys_client.execute(
-                        &format!("CREATE DATABASE \"{}\";", db_name),
-                        &[],
-                    )?;
-
-                    drop(sys_client); // Just to be explicit
-
-                    // Try connecting again
-                    let mut client = Client::connect(db_url, NoTls)?;
-                    Self::create_schema(&mut client)?;
-                    let migrations = Self::fetch_existing_migrations(&mut client)?;
-                    Ok(Database {
-                        conn: client,
-                        migrations,
-                    })
-                } else {
-                    Err(e)
-                }
-            }
-        }
+
+    pub fn rollback(&mut self) -> Result<(), Error> {
+        self.backend.rollback()
+    }
+
+    /// Fetch the `n` most recently applied migrations, newest first. Used by the
+    /// `down` command to peel migrations off the top of the ledger.
+    pub fn fetch_applied_migrations_desc(
+        &mut self,
+        n: u32,
+    ) -> Result<Vec<MigrationEntry>, Error> {
+        self.backend.fetch_applied_migrations_desc(n)
     }
 
+    /// Apply a migration's up SQL and record it in the ledger. When a
+    /// transaction is open (see [`Database::begin`]) this participates in it;
+    /// on non-transactional backends it is committed immediately.
     pub fn run_new_migration(
-        transaction: &mut Transaction,
+        &mut self,
         migration: &MigrationEntry,
         sql: &str,
-    ) -> Result<i64, postgres::Error> {
-        transaction.batch_execute(sql)?;
-        let insert_query =
-            "INSERT INTO __portunus_migrations (filename, hash) VALUES ($1, $2) RETURNING id";
-        let row = transaction.query_one(insert_query, &[&migration.filename, &migration.hash])?;
-        Ok(row.get("id"))
+    ) -> Result<i64, Error> {
+        self.backend.batch_execute(sql)?;
+        self.backend.insert_migration(migration)
+    }
+
+    /// Reverse a single migration: run its `down.sql` and drop the matching
+    /// ledger row. On backends with transactional DDL the caller batches several
+    /// of these inside one transaction so a failing down script leaves the
+    /// ledger untouched; elsewhere each one is committed as it runs.
+    pub fn rollback_migration(&mut self, down_sql: &str, filename: &str) -> Result<(), Error> {
+        self.backend.batch_execute(down_sql)?;
+        self.backend.delete_migration(filename)
+    }
+
+    /// Drop a migration's ledger row without running a reversal script. Used to
+    /// force past a migration whose files are gone from disk, where there is no
+    /// `down.sql` left to execute.
+    pub fn forget_migration(&mut self, filename: &str) -> Result<(), Error> {
+        self.backend.delete_migration(filename)
+    }
+
+    /// Execute an arbitrary SQL batch against the current connection. Exposed so
+    /// programmable (closure) migrations registered through a [`crate::Runner`]
+    /// can drive DDL without a file.
+    pub fn batch_execute(&mut self, sql: &str) -> Result<(), Error> {
+        self.backend.batch_execute(sql)
+    }
+
+    /// Write a ledger row for an already-applied migration, returning its id.
+    /// Used by function migrations, whose up step runs a closure rather than a
+    /// SQL batch.
+    pub fn record_migration(&mut self, entry: &MigrationEntry) -> Result<i64, Error> {
+        self.backend.insert_migration(entry)
     }
 }
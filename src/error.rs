@@ -0,0 +1,58 @@
+use std::fmt;
+
+/// Backend-agnostic error type. Each driver's native error is wrapped so the
+/// rest of the crate can speak a single `Result<_, Error>` regardless of which
+/// `MigrationBackend` is in play.
+#[derive(Debug)]
+pub enum Error {
+    #[cfg(feature = "postgres")]
+    Postgres(postgres::Error),
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+    #[cfg(feature = "mysql")]
+    Mysql(mysql::Error),
+    /// The connection URL scheme did not match any compiled-in backend.
+    UnsupportedScheme(String),
+    /// A non-driver failure raised by the crate itself.
+    Message(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "postgres")]
+            Error::Postgres(e) => write!(f, "{}", e),
+            #[cfg(feature = "sqlite")]
+            Error::Sqlite(e) => write!(f, "{}", e),
+            #[cfg(feature = "mysql")]
+            Error::Mysql(e) => write!(f, "{}", e),
+            Error::UnsupportedScheme(scheme) => {
+                write!(f, "no backend compiled in for URL scheme `{}`", scheme)
+            }
+            Error::Message(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(feature = "postgres")]
+impl From<postgres::Error> for Error {
+    fn from(e: postgres::Error) -> Self {
+        Error::Postgres(e)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Sqlite(e)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl From<mysql::Error> for Error {
+    fn from(e: mysql::Error) -> Self {
+        Error::Mysql(e)
+    }
+}
@@ -0,0 +1,10 @@
+pub mod backend;
+pub mod cli;
+pub mod config;
+pub mod database;
+pub mod error;
+pub mod migrations;
+pub mod runner;
+
+pub use error::Error;
+pub use runner::{Migration, Runner};
@@ -1,5 +1,5 @@
 use crate::database::{Database, MigrationEntry};
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{NaiveDateTime, Utc};
 use colored::Colorize;
 use regex::Regex;
 use std::{
@@ -12,39 +12,117 @@ use std::{
 use tabled::{
     Table,
     Tabled,
-    settings::{Settings, Style},
+    settings::{Color, Modify, Style, object::Cell},
 };
 use walkdir::WalkDir;
 
-#[derive(Tabled)]
-struct MigrationTablePrint {
-    /* since this can only be fetched from the db instance this cannot be null */
-    id: i64,
-    filename: String,
-    hash: String,
-    timestamp: DateTime<Utc>,
+/// The relationship between an on-disk migration and the ledger.
+enum MigrationState {
+    /// On disk and in the ledger with matching hashes.
+    Applied,
+    /// On disk but not yet in the ledger.
+    Pending,
+    /// On disk and in the ledger, but the up file has drifted.
+    Changed,
+    /// In the ledger but the file is gone from disk.
+    Missing,
 }
 
-impl From<&MigrationEntry> for MigrationTablePrint {
-    fn from(value: &MigrationEntry) -> Self {
-        MigrationTablePrint {
-            id: value.id.unwrap(),
-            filename: value.filename.clone(),
-            hash: value.hash.clone(),
-            timestamp: value.timestamp.unwrap(),
+impl MigrationState {
+    /// Plain label for the status cell. Color is applied separately via
+    /// [`MigrationState::color`] so `tabled` measures the real text width.
+    fn label(&self) -> &'static str {
+        match self {
+            MigrationState::Applied => "Applied",
+            MigrationState::Pending => "Pending",
+            MigrationState::Changed => "Changed",
+            MigrationState::Missing => "Missing",
+        }
+    }
+
+    /// The color `tabled` should paint this status with. Handing `tabled` a
+    /// [`Color`] rather than a pre-escaped string keeps column widths correct.
+    fn color(&self) -> Color {
+        match self {
+            MigrationState::Applied => Color::FG_GREEN,
+            MigrationState::Pending => Color::FG_YELLOW,
+            MigrationState::Changed => Color::BOLD | Color::FG_RED,
+            MigrationState::Missing => Color::BOLD | Color::FG_MAGENTA,
         }
     }
+
+    /// Whether this state should fail a `--check` run, i.e. the DB is not in
+    /// sync with what is on disk.
+    fn is_blocking(&self) -> bool {
+        matches!(self, MigrationState::Pending | MigrationState::Changed)
+    }
 }
-pub fn get_migration_status(db: &Database) {
-    let table_config = Settings::default().with(Style::psql());
-    let migs: Vec<MigrationTablePrint> = db
+
+#[derive(Tabled)]
+struct MigrationStatusRow {
+    filename: String,
+    status: String,
+    hash: String,
+}
+
+pub fn get_migration_status(db: &Database, migration_dir: &Path, check: bool) {
+    // Index both sides by ledger filename so we can diff them.
+    let disk: BTreeMap<String, MigrationEntry> = discover_migrations(migration_dir)
+        .iter()
+        .map(|source| {
+            let entry = MigrationEntry::new(source);
+            (entry.filename.clone(), entry)
+        })
+        .collect();
+    let applied: BTreeMap<String, MigrationEntry> = db
         .get_migrations()
         .iter()
-        .map(|x| MigrationTablePrint::from(x))
-        .collect::<Vec<MigrationTablePrint>>();
-    let table = Table::new(migs).with(table_config).to_string();
+        .map(|m| (m.filename.clone(), m.clone()))
+        .collect();
+
+    // Union of both key sets, sorted and de-duplicated.
+    let mut filenames: Vec<&String> = disk.keys().chain(applied.keys()).collect();
+    filenames.sort();
+    filenames.dedup();
+
+    let mut rows = Vec::new();
+    let mut states = Vec::new();
+    let mut blocking = false;
+    for filename in filenames {
+        let (state, hash) = match (disk.get(filename), applied.get(filename)) {
+            (Some(on_disk), Some(in_db)) => {
+                if on_disk.hash == in_db.hash {
+                    (MigrationState::Applied, on_disk.hash.clone())
+                } else {
+                    (MigrationState::Changed, on_disk.hash.clone())
+                }
+            }
+            (Some(on_disk), None) => (MigrationState::Pending, on_disk.hash.clone()),
+            (None, Some(in_db)) => (MigrationState::Missing, in_db.hash.clone()),
+            (None, None) => unreachable!(),
+        };
+        blocking |= state.is_blocking();
+        rows.push(MigrationStatusRow {
+            filename: filename.clone(),
+            status: state.label().to_string(),
+            hash,
+        });
+        states.push(state);
+    }
 
+    // Render with plain text, then paint each Status cell via a color-aware
+    // setting so the escape codes never count towards the measured width. The
+    // Status column is index 1; data rows start at 1 (row 0 is the header).
+    let mut table = Table::new(&rows);
+    table.with(Style::psql());
+    for (i, state) in states.iter().enumerate() {
+        table.with(Modify::new(Cell::new(i + 1, 1)).with(state.color()));
+    }
     println!("{}", table);
+
+    if check && blocking {
+        std::process::exit(1);
+    }
 }
 
 pub fn create_new_migration(migration_dir: &Path, migration_name: &str) {
@@ -97,28 +175,47 @@ pub fn create_new_migration(migration_dir: &Path, migration_name: &str) {
     println!("Created migration: {}", new_file_path.display());
 }
 
+/// Return the up-script path for a migration source: the file itself for a
+/// single-file migration, or `<dir>/up.sql` for a directory migration.
+fn up_sql_path(source: &Path) -> PathBuf {
+    if source.is_dir() {
+        source.join("up.sql")
+    } else {
+        source.to_path_buf()
+    }
+}
+
+/// Discover migration sources at the top level of `migration_dir`, sorted by
+/// name. A source is either a `.sql` file (single-file migration) or a
+/// directory containing an `up.sql` (paired up/down migration).
+fn discover_migrations(migration_dir: &Path) -> Vec<PathBuf> {
+    let mut sources: Vec<PathBuf> = WalkDir::new(migration_dir)
+        .min_depth(1)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(|entry| entry.ok()) // skip errored entries
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            if path.is_dir() {
+                path.join("up.sql").is_file()
+            } else {
+                path.extension().and_then(|e| e.to_str()) == Some("sql")
+            }
+        })
+        .collect();
+    sources.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    sources
+}
+
 pub fn run_migration(
     db: &mut Database,
     migration_dir: &Path,
     dry_run: bool,
     force: bool,
 ) {
-    let files: Vec<PathBuf> = WalkDir::new(migration_dir)
-        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
-        .into_iter()
-        .filter_map(|entry| entry.ok()) // skip errored entries
-        .filter(|entry| entry.file_type().is_file())
-        .filter(|entry| entry.path().extension().and_then(|e| e.to_str()) == Some("sql"))
-        .map(|entry| entry.into_path())
-        .collect();
+    let files: Vec<PathBuf> = discover_migrations(migration_dir);
 
-    let existing_entries = db
-        .get_migrations()
-        .iter()
-        .map(|m| m.clone())
-        .collect::<Vec<MigrationEntry>>();
-
-    let mut transaction = db.start_transaction().expect("Unable to start transaction");
+    let existing_entries = db.get_migrations().to_vec();
 
     let existing_map: BTreeMap<String, MigrationEntry> = existing_entries
         .into_iter()
@@ -128,7 +225,7 @@ pub fn run_migration(
     let mut current_map = BTreeMap::new();
 
     for file in &files {
-        let sql = fs::read_to_string(file).expect("Failed to read sql file");
+        let sql = fs::read_to_string(up_sql_path(file)).expect("Failed to read sql file");
         let entry = MigrationEntry::new(file); // computes hash internally
         current_map.insert(entry.filename.clone(), (entry, sql));
     }
@@ -152,9 +249,6 @@ pub fn run_migration(
                         continue;
                     } else {
                         eprintln!("{}", "... refusing to continue.".red().bold());
-                        transaction
-                            .rollback()
-                            .expect("Failed to rollback migration");
                         return;
                     }
                 }
@@ -178,51 +272,217 @@ pub fn run_migration(
                     continue;
                 } else {
                     eprintln!("{}", "... refusing to continue.".red().bold());
-                    transaction
-                        .rollback()
-                        .expect("Failed to rollback migration");
                     return;
                 }
             }
         }
     }
 
+    // MySQL commits implicitly on DDL, so a batch cannot be wrapped in one
+    // rollback-able transaction; apply file-by-file and warn instead.
+    let transactional = db.supports_transactional_ddl();
+    if !transactional {
+        eprintln!(
+            "{}",
+            "⚠ Backend does not support transactional DDL; a mid-batch failure cannot be auto-rolled-back."
+                .yellow()
+                .bold()
+        );
+        if dry_run {
+            eprintln!(
+                "{}",
+                "... dry-run would have to apply each file to observe it; aborting instead."
+                    .red()
+                    .bold()
+            );
+            return;
+        }
+    }
+
+    if transactional {
+        db.begin().expect("Unable to start transaction");
+    }
+
     let total = current_map.len();
     for (idx, (filename, (entry, sql))) in current_map.iter().enumerate() {
         if existing_map.contains_key(filename.as_str()) {
             continue;
         }
 
+        match db.run_new_migration(entry, sql) {
+            Ok(id) => println!(
+                "[{}] {} (ID: {})",
+                format!("{}/{}", idx + 1, total).truecolor(128, 128, 128),
+                filename.green(),
+                id.to_string().yellow()
+            ),
+            Err(e) => {
+                eprintln!(
+                    "{} {}\n{} {}",
+                    "✗ Failed to run migration:".red().bold(),
+                    filename.yellow(),
+                    "→ Error:".bright_red(),
+                    e
+                );
+                if transactional {
+                    db.rollback().expect("Failed to rollback migration");
+                }
+                return;
+            }
+        }
+    }
 
-        let id = Database::run_new_migration(&mut transaction, &entry, &sql);
-        if let Err(e) = id {
+    if dry_run {
+        db.rollback().ok();
+        return;
+    }
+    println!("Migration completed");
+    if transactional {
+        db.commit().ok();
+    }
+}
+
+pub fn rollback_migrations(db: &mut Database, migration_dir: &Path, steps: u32, force: bool) {
+    // Map on-disk migrations by their ledger filename so we can both recompute
+    // the up hash and locate the matching `down.sql`.
+    let disk: BTreeMap<String, MigrationEntry> = discover_migrations(migration_dir)
+        .iter()
+        .map(|source| {
+            let entry = MigrationEntry::new(source);
+            (entry.filename.clone(), entry)
+        })
+        .collect();
+
+    let applied = db
+        .fetch_applied_migrations_desc(steps)
+        .expect("Failed to query applied migrations");
+
+    if applied.is_empty() {
+        println!("Nothing to roll back");
+        return;
+    }
+
+    let transactional = db.supports_transactional_ddl();
+    if transactional {
+        db.begin().expect("Unable to start transaction");
+    }
+
+    let total = applied.len();
+    for (idx, mig) in applied.iter().enumerate() {
+        let on_disk = disk.get(&mig.filename);
+
+        // Refuse to roll back a migration whose up file has drifted from the
+        // hash we recorded when it was applied, unless forced.
+        match on_disk {
+            Some(entry) if entry.hash != mig.hash => {
+                eprintln!(
+                    "{}\n    → {}",
+                    "Stored hash does not match the on-disk up file.".red().bold(),
+                    mig.filename.yellow()
+                );
+                if !force {
+                    eprintln!("{}", "... refusing to roll back.".red().bold());
+                    if transactional {
+                        db.rollback().expect("Failed to rollback transaction");
+                    }
+                    return;
+                }
+                eprintln!(
+                    "{}",
+                    "... ignoring as user has asked me to force the rollback"
+                        .yellow()
+                        .bold()
+                );
+            }
+            None => {
+                eprintln!(
+                    "{}\n    → {}",
+                    "Migration is applied but its files are missing on disk."
+                        .red()
+                        .bold(),
+                    mig.filename.yellow()
+                );
+                if !force {
+                    eprintln!("{}", "... refusing to roll back.".red().bold());
+                    if transactional {
+                        db.rollback().expect("Failed to rollback transaction");
+                    }
+                    return;
+                }
+                // With the files gone there is no `down.sql` to run, so forcing
+                // simply drops the ledger row and moves on.
+                eprintln!(
+                    "{}",
+                    "... forcing: no down.sql on disk, dropping the ledger row only"
+                        .yellow()
+                        .bold()
+                );
+                if let Err(e) = db.forget_migration(&mig.filename) {
+                    eprintln!(
+                        "{} {}\n{} {}",
+                        "✗ Failed to roll back migration:".red().bold(),
+                        mig.filename.yellow(),
+                        "→ Error:".bright_red(),
+                        e
+                    );
+                    if transactional {
+                        db.rollback().expect("Failed to rollback transaction");
+                    }
+                    return;
+                }
+                println!(
+                    "[{}] {}",
+                    format!("{}/{}", idx + 1, total).truecolor(128, 128, 128),
+                    mig.filename.green()
+                );
+                continue;
+            }
+            _ => {}
+        }
+
+        let down_path = on_disk.and_then(|entry| entry.down_sql_path.clone());
+        let down_sql = match down_path {
+            Some(path) => fs::read_to_string(path).expect("Failed to read down.sql"),
+            None => {
+                eprintln!(
+                    "{}\n    → {}",
+                    "Migration has no down.sql and cannot be rolled back."
+                        .red()
+                        .bold(),
+                    mig.filename.yellow()
+                );
+                if transactional {
+                    db.rollback().expect("Failed to rollback transaction");
+                }
+                return;
+            }
+        };
+
+        if let Err(e) = db.rollback_migration(&down_sql, &mig.filename) {
             eprintln!(
                 "{} {}\n{} {}",
-                "✗ Failed to run migration:".red().bold(),
-                filename.yellow(),
+                "✗ Failed to roll back migration:".red().bold(),
+                mig.filename.yellow(),
                 "→ Error:".bright_red(),
                 e
             );
-            transaction
-                .rollback()
-                .expect("Failed to rollback migration");
+            if transactional {
+                db.rollback().expect("Failed to rollback transaction");
+            }
             return;
         }
-        let id = id.unwrap();
+
         println!(
-            "[{}] {} (ID: {})",
+            "[{}] {}",
             format!("{}/{}", idx + 1, total).truecolor(128, 128, 128),
-            filename.green(),
-            id.to_string().yellow()
+            mig.filename.green()
         );
     }
 
-    if dry_run {
-        transaction.rollback().ok();
-        return;
+    println!("Rollback completed");
+    if transactional {
+        db.commit().ok();
     }
-    println!("Migration completed");
-    transaction.commit().ok();
 }
 
 
@@ -0,0 +1,154 @@
+use crate::database::{Database, MigrationEntry};
+use crate::error::Error;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// The up or down step of a programmable (closure) migration.
+pub type MigrationStep = Box<dyn Fn(&mut Database) -> Result<(), Error>>;
+
+/// A single registered migration. `File` mirrors the on-disk `.sql` (or
+/// `up.sql`/`down.sql` directory) the CLI scans for; `Fn` is a compiled-in
+/// migration whose up/down steps are closures, letting downstream crates ship
+/// data backfills alongside SQL DDL.
+pub enum Migration {
+    File(PathBuf),
+    Fn {
+        tag: String,
+        up: MigrationStep,
+        down: Option<MigrationStep>,
+    },
+}
+
+impl Migration {
+    /// The ledger row this migration occupies. File migrations reuse
+    /// [`MigrationEntry::new`]; function migrations key on their `tag` and hash
+    /// the tag, since there is no file content to hash.
+    fn ledger_entry(&self) -> MigrationEntry {
+        match self {
+            Migration::File(path) => MigrationEntry::new(path),
+            Migration::Fn { tag, down, .. } => {
+                let mut hasher = Sha256::new();
+                hasher.update(tag);
+                let hash = format!("{:X}", hasher.finalize());
+                MigrationEntry {
+                    id: None,
+                    filename: tag.clone(),
+                    hash,
+                    timestamp: None,
+                    down_sql_path: None,
+                    has_down: down.is_some(),
+                }
+            }
+        }
+    }
+}
+
+/// An embeddable migration runner, modelled on `migrant_lib`. It holds an
+/// ordered list of [`Migration`]s plus the [`Database`] they run against, and
+/// applies the pending ones in the same ordered, transactional pass the CLI's
+/// `run_migration` uses.
+pub struct Runner {
+    migrations: Vec<Migration>,
+    db: Database,
+}
+
+impl Runner {
+    pub fn new(db: Database) -> Self {
+        Runner {
+            migrations: Vec::new(),
+            db,
+        }
+    }
+
+    /// Register a migration. Registration order is the apply order.
+    pub fn register(&mut self, migration: Migration) -> &mut Self {
+        self.migrations.push(migration);
+        self
+    }
+
+    /// The migrations already recorded in the ledger.
+    pub fn applied(&self) -> &Vec<MigrationEntry> {
+        self.db.get_migrations()
+    }
+
+    /// The registered migrations not yet present in the ledger, in order.
+    pub fn pending(&self) -> Vec<&Migration> {
+        let applied = self.applied_set();
+        self.migrations
+            .iter()
+            .filter(|m| !applied.contains(&m.ledger_entry().filename))
+            .collect()
+    }
+
+    /// Apply every pending migration in order. On a transactional backend the
+    /// whole pass runs in one transaction, so a failing step leaves the ledger
+    /// untouched; non-transactional backends commit each step as it succeeds.
+    pub fn run(&mut self) -> Result<(), Error> {
+        let applied = self.applied_set();
+        let transactional = self.db.supports_transactional_ddl();
+        if transactional {
+            self.db.begin()?;
+        }
+
+        // Detach the list so closures can borrow `self.db` mutably while we
+        // iterate; restore it regardless of outcome.
+        let migrations = std::mem::take(&mut self.migrations);
+        let outcome = self.apply_all(&migrations, &applied);
+        self.migrations = migrations;
+
+        match outcome {
+            Ok(()) => {
+                if transactional {
+                    self.db.commit()?;
+                }
+                // Refresh the cached ledger so a second `run`/`pending` on the
+                // same runner sees what this pass just applied instead of
+                // re-attempting it and tripping the UNIQUE(filename) constraint.
+                self.db.refresh()?;
+                Ok(())
+            }
+            Err(e) => {
+                if transactional {
+                    self.db.rollback()?;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn apply_all(&mut self, migrations: &[Migration], applied: &BTreeSet<String>) -> Result<(), Error> {
+        for migration in migrations {
+            let entry = migration.ledger_entry();
+            if applied.contains(&entry.filename) {
+                continue;
+            }
+            match migration {
+                Migration::File(path) => {
+                    let up_path = if path.is_dir() {
+                        path.join("up.sql")
+                    } else {
+                        path.clone()
+                    };
+                    let sql = fs::read_to_string(&up_path)
+                        .map_err(|e| Error::Message(format!("failed to read {:?}: {}", up_path, e)))?;
+                    self.db.run_new_migration(&entry, &sql)?;
+                }
+                Migration::Fn { up, .. } => {
+                    up(&mut self.db)?;
+                    self.db.record_migration(&entry)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn applied_set(&self) -> BTreeSet<String> {
+        self.db
+            .get_migrations()
+            .iter()
+            .map(|m| m.filename.clone())
+            .collect()
+    }
+}